@@ -0,0 +1,174 @@
+use crate::{Bloom, DefaultSipBuild};
+
+use std::cmp;
+use std::convert::TryFrom;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A lock-free variant of [`Bloom`], backed by `AtomicU64` words instead of
+/// a plain byte buffer, so `set`/`check`/`check_and_set` can be called from
+/// many threads through a shared `&self` without an external mutex. This
+/// suits high-throughput ingestion pipelines (e.g. dedup in a streaming
+/// system) where contention on a mutex around the whole filter would be the
+/// bottleneck.
+pub struct AtomicBloom<T: ?Sized, S = DefaultSipBuild> {
+    words: Vec<AtomicU64>,
+    bitmap_bits: u64,
+    k_num: u32,
+    build_hashers: [S; 2],
+
+    _phantom: PhantomData<T>,
+}
+
+impl<T: ?Sized> AtomicBloom<T, DefaultSipBuild> {
+    /// Create a new atomic bloom filter structure.
+    /// bitmap_size is the size in bytes (not bits) that will be allocated in
+    /// memory items_count is an estimation of the maximum number of items
+    /// to store. seed is a random value used to generate the hash
+    /// functions.
+    pub fn new_with_seed(
+        bitmap_size: usize,
+        items_count: usize,
+        seed: &[u8; 32],
+    ) -> Result<Self, &'static str> {
+        assert!(bitmap_size > 0 && items_count > 0);
+        let bitmap_bits = u64::try_from(bitmap_size)
+            .unwrap()
+            .checked_mul(8u64)
+            .unwrap();
+        let k_num = Self::optimal_k_num(bitmap_bits, items_count);
+        let word_count = (bitmap_bits as usize).div_ceil(64);
+        let words = (0..word_count).map(|_| AtomicU64::new(0)).collect();
+        let mut k1 = [0u8; 16];
+        let mut k2 = [0u8; 16];
+        k1.copy_from_slice(&seed[0..16]);
+        k2.copy_from_slice(&seed[16..32]);
+        let build_hashers = [DefaultSipBuild::new(k1), DefaultSipBuild::new(k2)];
+        Ok(Self {
+            words,
+            bitmap_bits,
+            k_num,
+            build_hashers,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Create a new atomic bloom filter structure.
+    /// items_count is an estimation of the maximum number of items to store.
+    /// fp_p is the wanted rate of false positives, in ]0.0, 1.0[
+    pub fn new_for_fp_rate_with_seed(
+        items_count: usize,
+        fp_p: f64,
+        seed: &[u8; 32],
+    ) -> Result<Self, &'static str> {
+        let bitmap_size = Bloom::<T>::compute_bitmap_size(items_count, fp_p);
+        Self::new_with_seed(bitmap_size, items_count, seed)
+    }
+
+    fn optimal_k_num(bitmap_bits: u64, items_count: usize) -> u32 {
+        let m = bitmap_bits as f64;
+        let n = items_count as f64;
+        let k_num = (m / n * f64::ln(2.0f64)).round() as u32;
+        cmp::max(k_num, 1)
+    }
+}
+
+impl<T: ?Sized, S: BuildHasher + Clone> AtomicBloom<T, S> {
+    /// Return the number of bits in the filter.
+    pub fn len(&self) -> u64 {
+        self.bitmap_bits
+    }
+
+    /// Test if there are no elements in the set.
+    pub fn is_empty(&self) -> bool {
+        self.words
+            .iter()
+            .all(|word| word.load(Ordering::Relaxed) == 0)
+    }
+
+    /// Return the number of hash functions used for `check` and `set`
+    pub fn number_of_hash_functions(&self) -> u32 {
+        self.k_num
+    }
+
+    /// Record the presence of an item. Safe to call concurrently from many
+    /// threads.
+    pub fn set(&self, item: &T)
+    where
+        T: Hash,
+    {
+        let mut hashes = [0u64, 0u64];
+        for k_i in 0..self.k_num {
+            let bit_offset = self.index(self.bloom_hash(&mut hashes, item, k_i));
+            let (word_index, bit) = (bit_offset / 64, bit_offset % 64);
+            self.words[word_index].fetch_or(1 << bit, Ordering::Relaxed);
+        }
+    }
+
+    /// Check if an item is present in the set.
+    /// There can be false positives, but no false negatives. Safe to call
+    /// concurrently from many threads.
+    pub fn check(&self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        let mut hashes = [0u64, 0u64];
+        for k_i in 0..self.k_num {
+            let bit_offset = self.index(self.bloom_hash(&mut hashes, item, k_i));
+            let (word_index, bit) = (bit_offset / 64, bit_offset % 64);
+            if self.words[word_index].load(Ordering::Relaxed) & (1 << bit) == 0 {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Record the presence of an item in the set, and return the previous
+    /// state of this item. Safe to call concurrently from many threads: the
+    /// membership check for each probe and the corresponding `set` happen
+    /// atomically together, via the previous value returned by
+    /// `fetch_or`.
+    pub fn check_and_set(&self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        let mut hashes = [0u64, 0u64];
+        let mut found = true;
+        for k_i in 0..self.k_num {
+            let bit_offset = self.index(self.bloom_hash(&mut hashes, item, k_i));
+            let (word_index, bit) = (bit_offset / 64, bit_offset % 64);
+            let previous = self.words[word_index].fetch_or(1 << bit, Ordering::Relaxed);
+            if previous & (1 << bit) == 0 {
+                found = false;
+            }
+        }
+        found
+    }
+
+    /// Clear all of the bits in the filter, removing all keys from the set.
+    pub fn clear(&self) {
+        for word in &self.words {
+            word.store(0, Ordering::Relaxed);
+        }
+    }
+
+    #[inline]
+    fn index(&self, hash: u64) -> usize {
+        (hash % self.bitmap_bits) as usize
+    }
+
+    fn bloom_hash(&self, hashes: &mut [u64; 2], item: &T, k_i: u32) -> u64
+    where
+        T: Hash,
+    {
+        if k_i < 2 {
+            let hash = self.build_hashers[k_i as usize].hash_one(item);
+            hashes[k_i as usize] = hash;
+            hash
+        } else {
+            (hashes[0]).wrapping_add((k_i as u64).wrapping_mul(hashes[1]))
+                % 0xFFFF_FFFF_FFFF_FFC5u64 //largest u64 prime
+        }
+    }
+}
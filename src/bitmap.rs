@@ -1,8 +1,13 @@
 use std::convert::{TryFrom, TryInto};
 use std::fmt::Debug;
 
-pub const VERSION: u8 = 1;
-pub const BITMAP_HEADER_SIZE: usize = 1 + 8 + 4 + 32;
+pub const VERSION: u8 = 4;
+/// version (1) + len_bytes (8) + k_num (4) + seed (32) + hasher_id (1) +
+/// layout_flag (1) + checksum (8)
+pub const BITMAP_HEADER_SIZE: usize = 1 + 8 + 4 + 32 + 1 + 1 + 8;
+/// Number of header bytes covered by the checksum, i.e. every header field
+/// except the checksum itself.
+const CHECKSUM_COVERED_SIZE: usize = BITMAP_HEADER_SIZE - 8;
 
 #[derive(Clone, Debug)]
 pub(crate) struct BitMap {
@@ -67,7 +72,92 @@ impl BitMap {
         header[13..][0..32].copy_from_slice(seed);
     }
 
-    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, &'static str> {
+    /// Identifies the `BuildHasher` family the filter was serialized with
+    /// (see `crate::HasherFingerprint`), so a filter can only be reopened
+    /// with a compatible hasher.
+    pub fn get_hasher_id(header: &[u8]) -> u8 {
+        header[45]
+    }
+
+    pub fn set_hasher_id(header: &mut [u8], hasher_id: u8) {
+        header[45] = hasher_id;
+    }
+
+    /// Nonzero when the filter was indexed with `hash & (bits - 1)` against
+    /// a power-of-two bit count rather than `hash % bits`, so a deserialized
+    /// filter picks the matching index computation (see `Bloom`'s `mask`).
+    pub fn get_layout_flag(header: &[u8]) -> u8 {
+        header[46]
+    }
+
+    pub fn set_layout_flag(header: &mut [u8], layout_flag: u8) {
+        header[46] = layout_flag;
+    }
+
+    fn get_checksum(header: &[u8]) -> u64 {
+        u64::from_le_bytes(header[47..][0..8].try_into().unwrap())
+    }
+
+    fn set_checksum(header: &mut [u8], checksum: u64) {
+        header[47..][0..8].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    /// Digest covering everything except the checksum field itself: the
+    /// version, length, k_num, seed, hasher id and layout flag, followed by
+    /// the bits.
+    /// Built out of the same position-mixing function used to update the
+    /// checksum incrementally in `update_checksum_byte`, so a single
+    /// changed byte can be folded in without rescanning the whole bitmap.
+    /// Not a cryptographic MAC, just corruption detection for bytes loaded
+    /// from disk or a memory-mapped file.
+    fn compute_checksum(header: &[u8], bits: &[u8]) -> u64 {
+        let mut checksum = 0u64;
+        for (position, &byte) in header[0..CHECKSUM_COVERED_SIZE]
+            .iter()
+            .chain(bits.iter())
+            .enumerate()
+        {
+            checksum ^= Self::byte_mix(position, byte);
+        }
+        checksum
+    }
+
+    /// Recompute and store the checksum over the current header metadata
+    /// and bits. Must be called after any mutation that should be reflected
+    /// in `as_slice`/`to_bytes`.
+    pub(crate) fn refresh_checksum(&mut self) {
+        let checksum = Self::compute_checksum(
+            &self.header_and_bits[0..BITMAP_HEADER_SIZE],
+            &self.header_and_bits[BITMAP_HEADER_SIZE..],
+        );
+        Self::set_checksum(&mut self.header_and_bits[0..BITMAP_HEADER_SIZE], checksum);
+    }
+
+    /// Mix a single byte's value and position into a checksum contribution,
+    /// so a single-byte change can update the checksum in constant time
+    /// instead of rescanning the whole bitmap (the hot path for `set`).
+    fn byte_mix(position: usize, value: u8) -> u64 {
+        (value as u64)
+            .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            .rotate_left((position % 64) as u32)
+    }
+
+    /// Fold a single changed bit-region byte into the stored checksum in
+    /// constant time, given its position among the bytes covered by
+    /// `compute_checksum` (i.e. offset by `CHECKSUM_COVERED_SIZE`, the
+    /// version/len/k_num/seed/hasher_id/layout_flag bytes that precede the
+    /// bits).
+    fn update_checksum_byte(&mut self, covered_position: usize, old: u8, new: u8) {
+        if old == new {
+            return;
+        }
+        let delta = Self::byte_mix(covered_position, old) ^ Self::byte_mix(covered_position, new);
+        let header = &mut self.header_and_bits[0..BITMAP_HEADER_SIZE];
+        let checksum = Self::get_checksum(header) ^ delta;
+        Self::set_checksum(header, checksum);
+    }
+
+    fn validate(bytes: &[u8]) -> Result<(), &'static str> {
         if bytes.len() < BITMAP_HEADER_SIZE {
             return Err("Invalid size");
         }
@@ -84,6 +174,14 @@ impl BitMap {
         if bits.len() != len_bytes {
             return Err("Invalid size");
         }
+        if Self::get_checksum(header) != Self::compute_checksum(header, bits) {
+            return Err("Checksum mismatch");
+        }
+        Ok(())
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, &'static str> {
+        Self::validate(&bytes)?;
         let res = Self {
             header_and_bits: bytes,
         };
@@ -91,22 +189,7 @@ impl BitMap {
     }
 
     pub fn from_slice(bytes: &[u8]) -> Result<Self, &'static str> {
-        if bytes.len() < BITMAP_HEADER_SIZE {
-            return Err("Invalid size");
-        }
-        let header = &bytes[0..BITMAP_HEADER_SIZE];
-        let bits = &bytes[BITMAP_HEADER_SIZE..];
-        if Self::get_version(header) != VERSION {
-            return Err("Version mismatch");
-        }
-        if Self::get_k_num(header) == 0 {
-            return Err("Invalid number of keys");
-        }
-        let len_bytes_u64 = Self::get_len_bytes(header);
-        let len_bytes: usize = len_bytes_u64.try_into().map_err(|_| "Too big")?;
-        if bits.len() != len_bytes {
-            return Err("Invalid size");
-        }
+        Self::validate(bytes)?;
         let res = Self {
             header_and_bits: bytes.to_vec(),
         };
@@ -134,25 +217,60 @@ impl BitMap {
     pub fn set(&mut self, bit_offset: usize) {
         let byte_offset = bit_offset / 8;
         let bit_shift = bit_offset % 8;
-        self.bits_mut()[byte_offset] |= 1 << bit_shift;
+        let old = self.bits()[byte_offset];
+        let new = old | (1 << bit_shift);
+        self.bits_mut()[byte_offset] = new;
+        self.update_checksum_byte(CHECKSUM_COVERED_SIZE + byte_offset, old, new);
     }
 
     pub fn clear(&mut self) {
         for byte in self.bits_mut().iter_mut() {
             *byte = 0;
         }
+        self.refresh_checksum();
     }
 
     pub fn set_all(&mut self) {
         for byte in self.bits_mut().iter_mut() {
             *byte = !0;
         }
+        self.refresh_checksum();
     }
 
     pub fn any(&self) -> bool {
         self.bits().iter().any(|&byte| byte != 0)
     }
 
+    /// Number of bits currently set, used to estimate the filter's
+    /// cardinality and achieved false-positive rate.
+    pub fn count_ones(&self) -> u64 {
+        self.bits().iter().map(|&byte| byte.count_ones() as u64).sum()
+    }
+
+    /// Set every bit that is set in `other`.
+    pub(crate) fn or_with(&mut self, other: &BitMap) {
+        for (a, b) in self.bits_mut().iter_mut().zip(other.bits().iter()) {
+            *a |= *b;
+        }
+        self.refresh_checksum();
+    }
+
+    /// Clear every bit that is not also set in `other`.
+    pub(crate) fn and_with(&mut self, other: &BitMap) {
+        for (a, b) in self.bits_mut().iter_mut().zip(other.bits().iter()) {
+            *a &= *b;
+        }
+        self.refresh_checksum();
+    }
+
+    /// Test whether every bit set in `self` is also set in `other`.
+    pub(crate) fn is_subset_of(&self, other: &BitMap) -> bool {
+        self.bits()
+            .iter()
+            .zip(other.bits().iter())
+            .all(|(a, b)| a & !b == 0)
+    }
+
     pub fn len_bits(&self) -> u64 {
         u64::try_from(self.bits().len())
             .unwrap()
@@ -160,6 +278,21 @@ impl BitMap {
             .unwrap()
     }
 
+    /// Read the byte at `byte_offset`, for draining a journal of touched
+    /// bytes.
+    pub(crate) fn byte(&self, byte_offset: usize) -> u8 {
+        self.bits()[byte_offset]
+    }
+
+    /// OR `value` into the byte at `byte_offset`, for applying a journal
+    /// drained from another, parameter-compatible bitmap.
+    pub(crate) fn or_byte(&mut self, byte_offset: usize, value: u8) {
+        let old = self.bits()[byte_offset];
+        let new = old | value;
+        self.bits_mut()[byte_offset] = new;
+        self.update_checksum_byte(CHECKSUM_COVERED_SIZE + byte_offset, old, new);
+    }
+
     #[doc(hidden)]
     pub fn realloc_large_heap_allocated_objects(mut self, f: fn(Vec<u8>) -> Vec<u8>) -> Self {
         let previous_len = self.header_and_bits.len();
@@ -169,3 +302,204 @@ impl BitMap {
         self
     }
 }
+
+/// A read-only, zero-copy view over bytes previously produced by
+/// [`BitMap::as_slice`]/`to_bytes`/`into_bytes` (e.g. a memory-mapped file),
+/// performing the same header and checksum validation as
+/// [`BitMap::from_slice`] without copying the bits into a fresh `Vec`.
+pub(crate) struct BitMapRef<'a> {
+    header_and_bits: &'a [u8],
+}
+
+impl<'a> BitMapRef<'a> {
+    pub fn from_slice(bytes: &'a [u8]) -> Result<Self, &'static str> {
+        BitMap::validate(bytes)?;
+        Ok(Self {
+            header_and_bits: bytes,
+        })
+    }
+
+    fn bits(&self) -> &[u8] {
+        &self.header_and_bits[BITMAP_HEADER_SIZE..]
+    }
+
+    pub fn header(&self) -> &[u8] {
+        &self.header_and_bits[0..BITMAP_HEADER_SIZE]
+    }
+
+    pub fn get(&self, bit_offset: usize) -> bool {
+        let byte_offset = bit_offset / 8;
+        let bit_shift = bit_offset % 8;
+        (self.bits()[byte_offset] & (1 << bit_shift)) != 0
+    }
+
+    pub fn len_bits(&self) -> u64 {
+        u64::try_from(self.bits().len())
+            .unwrap()
+            .checked_mul(8)
+            .unwrap()
+    }
+}
+
+/// A mutable, zero-copy view over bytes previously produced by
+/// [`BitMap::as_slice`]/`to_bytes`/`into_bytes` (e.g. a memory-mapped file
+/// opened read-write), supporting `set` as well as `get` with no
+/// allocation.
+///
+/// Mutating through this view does not update the header's integrity
+/// checksum, so once `set` has changed a byte, the bytes no longer pass
+/// the checksum validation of [`BitMapRef::from_slice`] or
+/// [`BitMap::from_slice`].
+pub(crate) struct BitMapRefMut<'a> {
+    header_and_bits: &'a mut [u8],
+}
+
+impl<'a> BitMapRefMut<'a> {
+    pub fn from_slice(bytes: &'a mut [u8]) -> Result<Self, &'static str> {
+        BitMap::validate(bytes)?;
+        Ok(Self {
+            header_and_bits: bytes,
+        })
+    }
+
+    fn bits(&self) -> &[u8] {
+        &self.header_and_bits[BITMAP_HEADER_SIZE..]
+    }
+
+    fn bits_mut(&mut self) -> &mut [u8] {
+        &mut self.header_and_bits[BITMAP_HEADER_SIZE..]
+    }
+
+    pub fn header(&self) -> &[u8] {
+        &self.header_and_bits[0..BITMAP_HEADER_SIZE]
+    }
+
+    pub fn get(&self, bit_offset: usize) -> bool {
+        let byte_offset = bit_offset / 8;
+        let bit_shift = bit_offset % 8;
+        (self.bits()[byte_offset] & (1 << bit_shift)) != 0
+    }
+
+    pub fn set(&mut self, bit_offset: usize) {
+        let byte_offset = bit_offset / 8;
+        let bit_shift = bit_offset % 8;
+        self.bits_mut()[byte_offset] |= 1 << bit_shift;
+    }
+
+    pub fn len_bits(&self) -> u64 {
+        u64::try_from(self.bits().len())
+            .unwrap()
+            .checked_mul(8)
+            .unwrap()
+    }
+}
+
+/// Backing storage for a [`crate::CountingBloom`]: an array of small
+/// saturating counters instead of single bits, so membership can be
+/// revoked with `decrement` as well as recorded with `increment`.
+#[derive(Clone, Debug)]
+pub(crate) struct CountingBitMap {
+    counters: Vec<u8>,
+    counter_bits: u32,
+    len_counters: usize,
+}
+
+impl CountingBitMap {
+    /// Create a new counting bitmap holding `len_counters` counters, each
+    /// `counter_bits` bits wide. `counter_bits` must be 4 or 8.
+    pub fn new(len_counters: usize, counter_bits: u32) -> Self {
+        assert!(counter_bits == 4 || counter_bits == 8);
+        let counters_per_byte = 8 / counter_bits as usize;
+        let len_bytes = len_counters.div_ceil(counters_per_byte);
+        Self {
+            counters: vec![0; len_bytes],
+            counter_bits,
+            len_counters,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len_counters
+    }
+
+    pub fn counter_bits(&self) -> u32 {
+        self.counter_bits
+    }
+
+    fn counters_per_byte(&self) -> usize {
+        8 / self.counter_bits as usize
+    }
+
+    fn max_value(&self) -> u8 {
+        ((1u16 << self.counter_bits) - 1) as u8
+    }
+
+    pub fn get(&self, index: usize) -> u8 {
+        let per_byte = self.counters_per_byte();
+        let shift = (index % per_byte) as u32 * self.counter_bits;
+        (self.counters[index / per_byte] >> shift) & self.max_value()
+    }
+
+    fn set(&mut self, index: usize, value: u8) {
+        let per_byte = self.counters_per_byte();
+        let shift = (index % per_byte) as u32 * self.counter_bits;
+        let mask = self.max_value() << shift;
+        let byte = &mut self.counters[index / per_byte];
+        *byte = (*byte & !mask) | ((value << shift) & mask);
+    }
+
+    /// Increment a counter, saturating at its maximum value.
+    pub fn increment(&mut self, index: usize) {
+        let value = self.get(index);
+        let max = self.max_value();
+        if value < max {
+            self.set(index, value + 1);
+        }
+    }
+
+    /// Decrement a counter, unless it is saturated: a saturated counter may
+    /// no longer reflect the true number of items hashed to it, so
+    /// decrementing it could drop it to zero while the item is still a
+    /// member of other, non-saturated counters, producing a false negative.
+    pub fn decrement(&mut self, index: usize) {
+        let value = self.get(index);
+        let max = self.max_value();
+        if value > 0 && value < max {
+            self.set(index, value - 1);
+        }
+    }
+
+    pub fn is_nonzero(&self, index: usize) -> bool {
+        self.get(index) != 0
+    }
+
+    pub fn clear(&mut self) {
+        for byte in self.counters.iter_mut() {
+            *byte = 0;
+        }
+    }
+
+    /// View the packed counters as an opaque slice of bytes, for serialization.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.counters
+    }
+
+    /// Rebuild a counting bitmap from bytes previously returned by `as_bytes`.
+    pub fn from_bytes(
+        counters: Vec<u8>,
+        len_counters: usize,
+        counter_bits: u32,
+    ) -> Result<Self, &'static str> {
+        assert!(counter_bits == 4 || counter_bits == 8);
+        let counters_per_byte = 8 / counter_bits as usize;
+        let expected_len = len_counters.div_ceil(counters_per_byte);
+        if counters.len() != expected_len {
+            return Err("Invalid size");
+        }
+        Ok(Self {
+            counters,
+            counter_bits,
+            len_counters,
+        })
+    }
+}
@@ -0,0 +1,223 @@
+use crate::bitmap::{BitMap, BitMapRef, BitMapRefMut};
+use crate::{DefaultSipBuild, HasherFingerprint};
+
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+/// A read-only, zero-copy view over a [`crate::Bloom`] previously
+/// serialized with `as_slice`/`to_bytes`/`into_bytes`, e.g. a
+/// memory-mapped file. `check` works directly against the borrowed bytes,
+/// with no allocation, unlike [`crate::Bloom::from_slice`] which copies
+/// them into a fresh owned filter.
+pub struct BloomRef<'a, T: ?Sized> {
+    bitmap: BitMapRef<'a>,
+    bitmap_bits: u64,
+    mask: Option<u64>,
+    k_num: u32,
+    build_hashers: [DefaultSipBuild; 2],
+
+    _phantom: PhantomData<T>,
+}
+
+impl<'a, T: ?Sized> BloomRef<'a, T> {
+    /// Borrow a serialized filter's bytes without copying them.
+    pub fn from_slice(bytes: &'a [u8]) -> Result<Self, &'static str> {
+        let bitmap = BitMapRef::from_slice(bytes)?;
+        let header = bitmap.header();
+        if BitMap::get_hasher_id(header) != DefaultSipBuild::HASHER_ID {
+            return Err("Filter was serialized with a different hasher");
+        }
+        let k_num = BitMap::get_k_num(header);
+        let seed = BitMap::get_seed(header);
+        let build_hashers = build_hashers_from_seed(&seed);
+        let bitmap_bits = bitmap.len_bits();
+        let mask = if BitMap::get_layout_flag(header) != 0 {
+            Some(bitmap_bits - 1)
+        } else {
+            None
+        };
+        Ok(Self {
+            bitmap,
+            bitmap_bits,
+            mask,
+            k_num,
+            build_hashers,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Return the number of bits in the filter.
+    pub fn len(&self) -> u64 {
+        self.bitmap_bits
+    }
+
+    /// Test if there are no elements in the set.
+    pub fn is_empty(&self) -> bool {
+        (0..self.bitmap_bits as usize).all(|i| !self.bitmap.get(i))
+    }
+
+    /// Return the number of hash functions used for `check`.
+    pub fn number_of_hash_functions(&self) -> u32 {
+        self.k_num
+    }
+
+    /// Check if an item is present in the set.
+    /// There can be false positives, but no false negatives.
+    pub fn check(&self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        let mut hashes = [0u64, 0u64];
+        for k_i in 0..self.k_num {
+            let bit_offset = self.index(self.bloom_hash(&mut hashes, item, k_i));
+            if !self.bitmap.get(bit_offset) {
+                return false;
+            }
+        }
+        true
+    }
+
+    #[inline]
+    fn index(&self, hash: u64) -> usize {
+        match self.mask {
+            Some(mask) => (hash & mask) as usize,
+            None => (hash % self.bitmap_bits) as usize,
+        }
+    }
+
+    fn bloom_hash(&self, hashes: &mut [u64; 2], item: &T, k_i: u32) -> u64
+    where
+        T: Hash,
+    {
+        bloom_hash(&self.build_hashers, hashes, item, k_i)
+    }
+}
+
+/// A mutable, zero-copy view over a [`crate::Bloom`] previously serialized
+/// with `as_slice`/`to_bytes`/`into_bytes`, e.g. a memory-mapped file
+/// opened read-write. `set`/`check` work directly against the borrowed
+/// bytes, with no allocation.
+///
+/// Setting a bit through this view does not update the header's integrity
+/// checksum, so once `set` has changed a bit, the underlying bytes are
+/// rejected with "Checksum mismatch" by [`BloomRef::from_slice`],
+/// [`crate::Bloom::from_slice`] and [`crate::Bloom::from_bytes`].
+pub struct BloomRefMut<'a, T: ?Sized> {
+    bitmap: BitMapRefMut<'a>,
+    bitmap_bits: u64,
+    mask: Option<u64>,
+    k_num: u32,
+    build_hashers: [DefaultSipBuild; 2],
+
+    _phantom: PhantomData<T>,
+}
+
+impl<'a, T: ?Sized> BloomRefMut<'a, T> {
+    /// Borrow a serialized filter's bytes without copying them.
+    pub fn from_slice(bytes: &'a mut [u8]) -> Result<Self, &'static str> {
+        let bitmap = BitMapRefMut::from_slice(bytes)?;
+        let header = bitmap.header();
+        if BitMap::get_hasher_id(header) != DefaultSipBuild::HASHER_ID {
+            return Err("Filter was serialized with a different hasher");
+        }
+        let k_num = BitMap::get_k_num(header);
+        let seed = BitMap::get_seed(header);
+        let build_hashers = build_hashers_from_seed(&seed);
+        let bitmap_bits = bitmap.len_bits();
+        let mask = if BitMap::get_layout_flag(header) != 0 {
+            Some(bitmap_bits - 1)
+        } else {
+            None
+        };
+        Ok(Self {
+            bitmap,
+            bitmap_bits,
+            mask,
+            k_num,
+            build_hashers,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Return the number of bits in the filter.
+    pub fn len(&self) -> u64 {
+        self.bitmap_bits
+    }
+
+    /// Test if there are no elements in the set.
+    pub fn is_empty(&self) -> bool {
+        (0..self.bitmap_bits as usize).all(|i| !self.bitmap.get(i))
+    }
+
+    /// Return the number of hash functions used for `check` and `set`.
+    pub fn number_of_hash_functions(&self) -> u32 {
+        self.k_num
+    }
+
+    /// Record the presence of an item.
+    pub fn set(&mut self, item: &T)
+    where
+        T: Hash,
+    {
+        let mut hashes = [0u64, 0u64];
+        for k_i in 0..self.k_num {
+            let bit_offset = self.index(self.bloom_hash(&mut hashes, item, k_i));
+            self.bitmap.set(bit_offset);
+        }
+    }
+
+    /// Check if an item is present in the set.
+    /// There can be false positives, but no false negatives.
+    pub fn check(&self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        let mut hashes = [0u64, 0u64];
+        for k_i in 0..self.k_num {
+            let bit_offset = self.index(self.bloom_hash(&mut hashes, item, k_i));
+            if !self.bitmap.get(bit_offset) {
+                return false;
+            }
+        }
+        true
+    }
+
+    #[inline]
+    fn index(&self, hash: u64) -> usize {
+        match self.mask {
+            Some(mask) => (hash & mask) as usize,
+            None => (hash % self.bitmap_bits) as usize,
+        }
+    }
+
+    fn bloom_hash(&self, hashes: &mut [u64; 2], item: &T, k_i: u32) -> u64
+    where
+        T: Hash,
+    {
+        bloom_hash(&self.build_hashers, hashes, item, k_i)
+    }
+}
+
+fn build_hashers_from_seed(seed: &[u8; 32]) -> [DefaultSipBuild; 2] {
+    let mut k1 = [0u8; 16];
+    let mut k2 = [0u8; 16];
+    k1.copy_from_slice(&seed[0..16]);
+    k2.copy_from_slice(&seed[16..32]);
+    [DefaultSipBuild::new(k1), DefaultSipBuild::new(k2)]
+}
+
+fn bloom_hash<T: ?Sized + Hash>(
+    build_hashers: &[DefaultSipBuild; 2],
+    hashes: &mut [u64; 2],
+    item: &T,
+    k_i: u32,
+) -> u64 {
+    if k_i < 2 {
+        let hash = build_hashers[k_i as usize].hash_one(item);
+        hashes[k_i as usize] = hash;
+        hash
+    } else {
+        (hashes[0]).wrapping_add((k_i as u64).wrapping_mul(hashes[1]))
+            % 0xFFFF_FFFF_FFFF_FFC5u64 //largest u64 prime
+    }
+}
@@ -0,0 +1,268 @@
+use crate::bitmap::CountingBitMap;
+
+use std::cmp;
+use std::convert::TryInto;
+use std::f64;
+use std::fmt::{self, Debug};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use siphasher::sip::SipHasher13;
+
+const VERSION: u8 = 1;
+/// version (1) + len_counters (8) + k_num (4) + seed (32) + counter_bits (1)
+const HEADER_SIZE: usize = 1 + 8 + 4 + 32 + 1;
+
+/// Width, in bits, of each saturating counter backing a [`CountingBloom`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterWidth {
+    /// 4-bit counters, saturating at 15. Halves the memory footprint of
+    /// 8-bit counters for the same number of slots, at the cost of saturating
+    /// (and thus becoming un-removable) sooner.
+    Four,
+    /// 8-bit counters, saturating at 255. The usual choice unless memory is
+    /// tight.
+    Eight,
+}
+
+impl CounterWidth {
+    fn bits(self) -> u32 {
+        match self {
+            CounterWidth::Four => 4,
+            CounterWidth::Eight => 8,
+        }
+    }
+}
+
+/// A Bloom filter variant that supports removing items as well as adding
+/// them, by replacing each single bit with a small saturating counter.
+///
+/// `insert` increments the `k` counters an item hashes to; `remove`
+/// decrements them; `check` reports the item as present only while all `k`
+/// counters are nonzero. A counter that has saturated at its maximum value
+/// is never decremented, since doing so could make `check` return a false
+/// negative for other items sharing that counter.
+#[derive(Clone)]
+pub struct CountingBloom<T: ?Sized> {
+    counters: CountingBitMap,
+    bitmap_bits: u64,
+    k_num: u32,
+    sips: [SipHasher13; 2],
+
+    _phantom: PhantomData<T>,
+}
+
+impl<T: ?Sized> Debug for CountingBloom<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Counting bloom filter with {} counters, {} hash functions and seed: {:?} ",
+            self.bitmap_bits,
+            self.k_num,
+            self.seed()
+        )
+    }
+}
+
+impl<T: ?Sized> CountingBloom<T> {
+    /// Create a new counting bloom filter structure.
+    /// bitmap_size is the size in bytes (not bits) that a plain `Bloom` with
+    /// the same number of slots would occupy; items_count is an estimation
+    /// of the maximum number of items to store. seed is a random value used
+    /// to generate the hash functions.
+    pub fn new_with_seed(
+        bitmap_size: usize,
+        items_count: usize,
+        counter_width: CounterWidth,
+        seed: &[u8; 32],
+    ) -> Result<Self, &'static str> {
+        assert!(bitmap_size > 0 && items_count > 0);
+        let bitmap_bits = (bitmap_size as u64).checked_mul(8u64).unwrap();
+        let k_num = Self::optimal_k_num(bitmap_bits, items_count);
+        let counters = CountingBitMap::new(bitmap_bits as usize, counter_width.bits());
+        let mut k1 = [0u8; 16];
+        let mut k2 = [0u8; 16];
+        k1.copy_from_slice(&seed[0..16]);
+        k2.copy_from_slice(&seed[16..32]);
+        let sips = [Self::sip_new(&k1), Self::sip_new(&k2)];
+        Ok(Self {
+            counters,
+            bitmap_bits,
+            k_num,
+            sips,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Create a new counting bloom filter structure.
+    /// items_count is an estimation of the maximum number of items to store.
+    /// fp_p is the wanted rate of false positives, in ]0.0, 1.0[, before any
+    /// items have been removed.
+    pub fn new_for_fp_rate_with_seed(
+        items_count: usize,
+        fp_p: f64,
+        counter_width: CounterWidth,
+        seed: &[u8; 32],
+    ) -> Result<Self, &'static str> {
+        let bitmap_size = crate::Bloom::<T>::compute_bitmap_size(items_count, fp_p);
+        Self::new_with_seed(bitmap_size, items_count, counter_width, seed)
+    }
+
+    /// Return the number of counters (slots) in the filter.
+    pub fn len(&self) -> u64 {
+        self.bitmap_bits
+    }
+
+    /// Test if there are no elements in the set.
+    pub fn is_empty(&self) -> bool {
+        (0..self.counters.len()).all(|i| !self.counters.is_nonzero(i))
+    }
+
+    /// Record the presence of an item, incrementing each of its k counters.
+    pub fn insert(&mut self, item: &T)
+    where
+        T: Hash,
+    {
+        let mut hashes = [0u64, 0u64];
+        for k_i in 0..self.k_num {
+            let index = (self.bloom_hash(&mut hashes, item, k_i) % self.bitmap_bits) as usize;
+            self.counters.increment(index);
+        }
+    }
+
+    /// Remove an item, decrementing each of its k counters — but only if
+    /// the item currently checks present. Removing an item that was never
+    /// inserted (or already removed) would otherwise decrement counters it
+    /// shares with real members, which could drop one to zero and produce a
+    /// false negative for those members. Saturated counters are still left
+    /// untouched, as in `CountingBitMap::decrement`.
+    pub fn remove(&mut self, item: &T)
+    where
+        T: Hash,
+    {
+        let mut hashes = [0u64, 0u64];
+        let mut indexes = Vec::with_capacity(self.k_num as usize);
+        for k_i in 0..self.k_num {
+            let index = (self.bloom_hash(&mut hashes, item, k_i) % self.bitmap_bits) as usize;
+            indexes.push(index);
+        }
+        if indexes.iter().any(|&index| !self.counters.is_nonzero(index)) {
+            return;
+        }
+        for index in indexes {
+            self.counters.decrement(index);
+        }
+    }
+
+    /// Check if an item is present in the set.
+    /// There can be false positives, but no false negatives.
+    pub fn check(&self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        let mut hashes = [0u64, 0u64];
+        for k_i in 0..self.k_num {
+            let index = (self.bloom_hash(&mut hashes, item, k_i) % self.bitmap_bits) as usize;
+            if !self.counters.is_nonzero(index) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Clear all of the counters in the filter, removing all keys from the set.
+    pub fn clear(&mut self) {
+        self.counters.clear()
+    }
+
+    /// Return the number of hash functions used for `check`, `insert` and `remove`.
+    pub fn number_of_hash_functions(&self) -> u32 {
+        self.k_num
+    }
+
+    /// Return the seed used to generate the hash functions.
+    pub fn seed(&self) -> [u8; 32] {
+        let mut seed = [0u8; 32];
+        seed[0..16].copy_from_slice(&self.sips[0].key());
+        seed[16..32].copy_from_slice(&self.sips[1].key());
+        seed
+    }
+
+    /// Serialize the filter, including its counters, k_num and seed, to a
+    /// newly allocated byte vector that can later be passed to
+    /// [`CountingBloom::from_bytes`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.counters.as_bytes().len());
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.counters.len().to_le_bytes()[0..8]);
+        bytes.extend_from_slice(&self.k_num.to_le_bytes());
+        bytes.extend_from_slice(&self.seed());
+        bytes.push(self.counters.counter_bits() as u8);
+        bytes.extend_from_slice(self.counters.as_bytes());
+        bytes
+    }
+
+    /// Rebuild a filter from bytes previously returned by
+    /// [`CountingBloom::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        if bytes.len() < HEADER_SIZE {
+            return Err("Invalid size");
+        }
+        if bytes[0] != VERSION {
+            return Err("Version mismatch");
+        }
+        let len_counters =
+            u64::from_le_bytes(bytes[1..9].try_into().unwrap()) as usize;
+        let k_num = u32::from_le_bytes(bytes[9..13].try_into().unwrap());
+        if k_num == 0 {
+            return Err("Invalid number of keys");
+        }
+        let seed: [u8; 32] = bytes[13..45].try_into().unwrap();
+        let counter_bits = u32::from(bytes[45]);
+        if counter_bits != 4 && counter_bits != 8 {
+            return Err("Invalid counter width");
+        }
+        let counters =
+            CountingBitMap::from_bytes(bytes[HEADER_SIZE..].to_vec(), len_counters, counter_bits)?;
+        let mut k1 = [0u8; 16];
+        let mut k2 = [0u8; 16];
+        k1.copy_from_slice(&seed[0..16]);
+        k2.copy_from_slice(&seed[16..32]);
+        let sips = [Self::sip_new(&k1), Self::sip_new(&k2)];
+        Ok(Self {
+            counters,
+            bitmap_bits: len_counters as u64,
+            k_num,
+            sips,
+            _phantom: PhantomData,
+        })
+    }
+
+    #[inline]
+    fn sip_new(key: &[u8; 16]) -> SipHasher13 {
+        SipHasher13::new_with_key(key)
+    }
+
+    fn optimal_k_num(bitmap_bits: u64, items_count: usize) -> u32 {
+        let m = bitmap_bits as f64;
+        let n = items_count as f64;
+        let k_num = (m / n * f64::ln(2.0f64)).round() as u32;
+        cmp::max(k_num, 1)
+    }
+
+    fn bloom_hash(&self, hashes: &mut [u64; 2], item: &T, k_i: u32) -> u64
+    where
+        T: Hash,
+    {
+        if k_i < 2 {
+            let sip = &mut self.sips[k_i as usize].clone();
+            item.hash(sip);
+            let hash = sip.finish();
+            hashes[k_i as usize] = hash;
+            hash
+        } else {
+            (hashes[0]).wrapping_add((k_i as u64).wrapping_mul(hashes[1]))
+                % 0xFFFF_FFFF_FFFF_FFC5u64 //largest u64 prime
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::Bloom;
+
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::hash::Hash;
+
+/// A wrapper around [`Bloom`] that records which backing bytes have flipped
+/// a bit from 0 to 1 since the last [`checkpoint`](Self::checkpoint), so a
+/// large on-disk filter can be patched with a compact byte-level delta
+/// instead of rewriting the whole [`Bloom::as_slice`] blob on every write.
+/// Because a `Bloom` insert only ever sets bits, these deltas are monotonic
+/// and can be merged by union, so a writer can flush successive diffs
+/// without ever rewriting earlier ones.
+pub struct JournaledBloom<T: ?Sized> {
+    inner: Bloom<T>,
+    dirty: BTreeSet<u32>,
+}
+
+impl<T: ?Sized> JournaledBloom<T> {
+    /// Wrap an existing filter, starting with an empty journal.
+    pub fn from_parts(inner: Bloom<T>) -> Self {
+        Self {
+            inner,
+            dirty: BTreeSet::new(),
+        }
+    }
+
+    /// Record the presence of an item, marking as dirty every byte in which
+    /// it flips a bit from 0 to 1.
+    pub fn set(&mut self, item: &T)
+    where
+        T: Hash,
+    {
+        let mut hashes = [0u64, 0u64];
+        for k_i in 0..self.inner.number_of_hash_functions() {
+            let hash = self.inner.bloom_hash(&mut hashes, item, k_i);
+            let bit_offset = self.inner.index(hash);
+            if !self.inner.bitmap().get(bit_offset) {
+                self.inner.bitmap_mut().set(bit_offset);
+                let byte_offset = u32::try_from(bit_offset / 8).unwrap();
+                self.dirty.insert(byte_offset);
+            }
+        }
+    }
+
+    /// Check if an item is present in the set.
+    pub fn check(&self, item: &T) -> bool
+    where
+        T: Hash,
+    {
+        self.inner.check(item)
+    }
+
+    /// Mark the filter's current contents as the new baseline, discarding
+    /// any pending journal entries without returning them.
+    pub fn checkpoint(&mut self) {
+        self.dirty.clear();
+    }
+
+    /// Drain the journal, returning the `(byte_offset, byte_value)` pairs
+    /// for every byte touched since the last checkpoint or drain, in
+    /// ascending offset order, and checkpointing the filter.
+    pub fn drain_journal(&mut self) -> Vec<(u32, u8)> {
+        let entries = self
+            .dirty
+            .iter()
+            .map(|&byte_offset| (byte_offset, self.inner.bitmap().byte(byte_offset as usize)))
+            .collect();
+        self.checkpoint();
+        entries
+    }
+
+    /// Apply a journal drained from another, parameter-compatible filter,
+    /// OR-ing the changed bytes into this filter's bitmap.
+    pub fn apply_journal(&mut self, journal: &[(u32, u8)]) {
+        for &(byte_offset, byte_value) in journal {
+            self.inner
+                .bitmap_mut()
+                .or_byte(byte_offset as usize, byte_value);
+        }
+    }
+
+    /// Consume the wrapper, returning the underlying filter.
+    pub fn into_inner(self) -> Bloom<T> {
+        self.inner
+    }
+
+    /// Borrow the underlying filter.
+    pub fn inner(&self) -> &Bloom<T> {
+        &self.inner
+    }
+}
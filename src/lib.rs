@@ -9,11 +9,23 @@
 mod bitmap;
 use bitmap::*;
 
+mod counting_bloom;
+pub use counting_bloom::{CounterWidth, CountingBloom};
+
+mod journal;
+pub use journal::JournaledBloom;
+
+mod atomic_bloom;
+pub use atomic_bloom::AtomicBloom;
+
+mod bloom_ref;
+pub use bloom_ref::{BloomRef, BloomRefMut};
+
 use std::cmp;
 use std::convert::TryFrom;
 use std::f64;
 use std::fmt::{self, Debug};
-use std::hash::{Hash, Hasher};
+use std::hash::{BuildHasher, Hash};
 use std::marker::PhantomData;
 
 #[cfg(feature = "random")]
@@ -28,30 +40,72 @@ pub mod reexports {
     pub use siphasher::reexports::serde;
 }
 
+/// The default [`BuildHasher`] used by [`Bloom`]: SipHash-1-3, seeded from a
+/// 16-byte key drawn from the filter's 32-byte seed. This is the secure
+/// choice for adversarial inputs; parameterize `Bloom` over a different
+/// `S: BuildHasher + Clone` (FNV, ahash, xxHash...) to trade that resistance
+/// for speed when inputs are trusted.
+#[derive(Clone, PartialEq)]
+pub struct DefaultSipBuild {
+    key: [u8; 16],
+}
+
+impl DefaultSipBuild {
+    fn new(key: [u8; 16]) -> Self {
+        Self { key }
+    }
+}
+
+impl BuildHasher for DefaultSipBuild {
+    type Hasher = SipHasher13;
+
+    fn build_hasher(&self) -> SipHasher13 {
+        SipHasher13::new_with_key(&self.key)
+    }
+}
+
+/// Identifies a `BuildHasher` family so it can be persisted in a filter's
+/// header and checked on reload: a filter serialized with one hasher family
+/// should not silently be reopened as if it used a different one, since
+/// `bloom_hash` would then probe completely different bit positions than
+/// the ones it was populated with. `0` is reserved to mean "unspecified".
+pub trait HasherFingerprint {
+    /// A value uniquely identifying this hasher family. Pick any value
+    /// other than `0` that isn't already used by another `BuildHasher` this
+    /// crate's callers persist filters with.
+    const HASHER_ID: u8;
+}
+
+impl HasherFingerprint for DefaultSipBuild {
+    const HASHER_ID: u8 = 1;
+}
+
 /// Bloom filter structure
 #[derive(Clone)]
-pub struct Bloom<T: ?Sized> {
+pub struct Bloom<T: ?Sized, S = DefaultSipBuild> {
     bitmap: BitMap,
     bitmap_bits: u64,
+    // When the bitmap size has been rounded up to a power of two, holds
+    // `bitmap_bits - 1`, letting probes replace `hash % bitmap_bits` with
+    // the much cheaper `hash & mask`.
+    mask: Option<u64>,
     k_num: u32,
-    sips: [SipHasher13; 2],
+    build_hashers: [S; 2],
 
     _phantom: PhantomData<T>,
 }
 
-impl<T: ?Sized> Debug for Bloom<T> {
+impl<T: ?Sized, S> Debug for Bloom<T, S> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Bloom filter with {} bits, {} hash functions and seed: {:?} ",
-            self.bitmap_bits,
-            self.k_num,
-            self.seed()
+            "Bloom filter with {} bits and {} hash functions",
+            self.bitmap_bits, self.k_num,
         )
     }
 }
 
-impl<T: ?Sized> Bloom<T> {
+impl<T: ?Sized> Bloom<T, DefaultSipBuild> {
     /// Create a new bloom filter structure.
     /// bitmap_size is the size in bytes (not bits) that will be allocated in
     /// memory items_count is an estimation of the maximum number of items
@@ -67,18 +121,19 @@ impl<T: ?Sized> Bloom<T> {
             .unwrap()
             .checked_mul(8u64)
             .unwrap();
-        let k_num = Self::optimal_k_num(bitmap_bits, items_count);
+        let k_num = optimal_k_num(bitmap_bits, items_count);
         let bitmap = BitMap::new(bitmap_size);
         let mut k1 = [0u8; 16];
         let mut k2 = [0u8; 16];
         k1.copy_from_slice(&seed[0..16]);
         k2.copy_from_slice(&seed[16..32]);
-        let sips = [Self::sip_new(&k1), Self::sip_new(&k2)];
+        let build_hashers = [DefaultSipBuild::new(k1), DefaultSipBuild::new(k2)];
         let mut res = Self {
             bitmap,
             bitmap_bits,
+            mask: None,
             k_num,
-            sips,
+            build_hashers,
             _phantom: PhantomData,
         };
         res.sync();
@@ -118,6 +173,55 @@ impl<T: ?Sized> Bloom<T> {
         Bloom::new_with_seed(bitmap_size, items_count, seed)
     }
 
+    /// Create a new bloom filter structure sized for a target false-positive
+    /// rate, like `new_for_fp_rate_with_seed`, but rounding the bitmap size
+    /// up to the next power of two bits. This replaces the `hash %
+    /// bitmap_bits` in every probe with a single `hash & mask`, at the cost
+    /// of the bitmap possibly being larger than requested (which can only
+    /// make the achieved false-positive rate *better* than `fp_p`, never
+    /// worse).
+    pub fn new_pow2_for_fp_rate_with_seed(
+        items_count: usize,
+        fp_p: f64,
+        seed: &[u8; 32],
+    ) -> Result<Self, &'static str> {
+        let bitmap_size = Self::compute_bitmap_size(items_count, fp_p);
+        Bloom::new_pow2_with_seed(bitmap_size, items_count, seed)
+    }
+
+    /// Create a new bloom filter structure. Like `new_with_seed`, but rounds
+    /// the bitmap size up to the next power of two bits so that probes can
+    /// use `hash & mask` instead of `hash % bitmap_bits`.
+    pub fn new_pow2_with_seed(
+        bitmap_size: usize,
+        items_count: usize,
+        seed: &[u8; 32],
+    ) -> Result<Self, &'static str> {
+        assert!(bitmap_size > 0 && items_count > 0);
+        let requested_bits = u64::try_from(bitmap_size)
+            .unwrap()
+            .checked_mul(8u64)
+            .unwrap();
+        let bitmap_bits = requested_bits.next_power_of_two();
+        let k_num = optimal_k_num(bitmap_bits, items_count);
+        let bitmap = BitMap::new((bitmap_bits / 8) as usize);
+        let mut k1 = [0u8; 16];
+        let mut k2 = [0u8; 16];
+        k1.copy_from_slice(&seed[0..16]);
+        k2.copy_from_slice(&seed[16..32]);
+        let build_hashers = [DefaultSipBuild::new(k1), DefaultSipBuild::new(k2)];
+        let mut res = Self {
+            bitmap,
+            bitmap_bits,
+            mask: Some(bitmap_bits - 1),
+            k_num,
+            build_hashers,
+            _phantom: PhantomData,
+        };
+        res.sync();
+        Ok(res)
+    }
+
     /// Compute a recommended bitmap size for items_count items
     /// and a fp_p rate of false positives.
     /// fp_p obviously has to be within the ]0.0, 1.0[ range.
@@ -129,6 +233,111 @@ impl<T: ?Sized> Bloom<T> {
         ((items_count as f64) * f64::ln(fp_p) / (-8.0 * log2_2)).ceil() as usize
     }
 
+    /// Create a bloom filter from a slice of bytes, previously generated with `as_slice`.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, &'static str> {
+        let bitmap = BitMap::from_slice(bytes)?;
+        Self::from_bitmap(bitmap)
+    }
+
+    /// Transform a byte vector into a bloom filter.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, &'static str> {
+        let bitmap = BitMap::from_bytes(bytes)?;
+        Self::from_bitmap(bitmap)
+    }
+
+    fn from_bitmap(bitmap: BitMap) -> Result<Self, &'static str> {
+        let header = bitmap.header();
+        if BitMap::get_hasher_id(header) != DefaultSipBuild::HASHER_ID {
+            return Err("Filter was serialized with a different hasher");
+        }
+        let k_num = BitMap::get_k_num(header);
+        let seed = BitMap::get_seed(header);
+        let mut k1 = [0u8; 16];
+        let mut k2 = [0u8; 16];
+        k1.copy_from_slice(&seed[0..16]);
+        k2.copy_from_slice(&seed[16..32]);
+        let build_hashers = [DefaultSipBuild::new(k1), DefaultSipBuild::new(k2)];
+        let bitmap_bits = bitmap.len_bits();
+        let mask = if BitMap::get_layout_flag(header) != 0 {
+            Some(bitmap_bits - 1)
+        } else {
+            None
+        };
+        let res = Self {
+            bitmap,
+            bitmap_bits,
+            mask,
+            k_num,
+            build_hashers,
+            _phantom: PhantomData,
+        };
+        Ok(res)
+    }
+
+    /// Return the seed used to generate the hash functions
+    pub fn seed(&self) -> [u8; 32] {
+        let mut seed = [0u8; 32];
+        seed[0..16].copy_from_slice(&self.build_hashers[0].key);
+        seed[16..32].copy_from_slice(&self.build_hashers[1].key);
+        seed
+    }
+
+    fn sync(&mut self) {
+        let seed = self.seed();
+        let header = self.bitmap.header_mut();
+        BitMap::set_k_num(header, self.k_num);
+        BitMap::set_seed(header, &seed);
+        BitMap::set_hasher_id(header, DefaultSipBuild::HASHER_ID);
+        BitMap::set_layout_flag(header, self.mask.is_some() as u8);
+        self.bitmap.refresh_checksum();
+    }
+}
+
+/// Pick a number of hash functions expected to minimize the false-positive
+/// rate for a filter of `bitmap_bits` bits holding `items_count` items.
+fn optimal_k_num(bitmap_bits: u64, items_count: usize) -> u32 {
+    let m = bitmap_bits as f64;
+    let n = items_count as f64;
+    let k_num = (m / n * f64::ln(2.0f64)).round() as u32;
+    cmp::max(k_num, 1)
+}
+
+impl<T: ?Sized, S: BuildHasher + Clone> Bloom<T, S> {
+    /// Create a new bloom filter structure using a custom pair of build
+    /// hashers (e.g. FNV, ahash, xxHash) instead of the default SipHash-1-3,
+    /// trading `DefaultSipBuild`'s resistance to adversarial inputs for
+    /// speed when inputs are trusted. bitmap_size is the size in bytes (not
+    /// bits) that will be allocated in memory; items_count is an estimation
+    /// of the maximum number of items to store.
+    ///
+    /// Filters built this way can't be serialized with `as_slice`/`to_bytes`
+    /// and reopened with `from_slice`/`from_bytes`, since those only persist
+    /// and verify `DefaultSipBuild`'s hasher id.
+    pub fn new_with_hashers(
+        bitmap_size: usize,
+        items_count: usize,
+        build_hashers: [S; 2],
+    ) -> Result<Self, &'static str> {
+        assert!(bitmap_size > 0 && items_count > 0);
+        let bitmap_bits = u64::try_from(bitmap_size)
+            .unwrap()
+            .checked_mul(8u64)
+            .unwrap();
+        let k_num = optimal_k_num(bitmap_bits, items_count);
+        let mut bitmap = BitMap::new(bitmap_size);
+        let header = bitmap.header_mut();
+        BitMap::set_k_num(header, k_num);
+        bitmap.refresh_checksum();
+        Ok(Self {
+            bitmap,
+            bitmap_bits,
+            mask: None,
+            k_num,
+            build_hashers,
+            _phantom: PhantomData,
+        })
+    }
+
     /// Return the number of bits in the filter.
     pub fn len(&self) -> u64 {
         self.bitmap.len_bits()
@@ -141,7 +350,7 @@ impl<T: ?Sized> Bloom<T> {
     {
         let mut hashes = [0u64, 0u64];
         for k_i in 0..self.k_num {
-            let bit_offset = (self.bloom_hash(&mut hashes, item, k_i) % self.bitmap_bits) as usize;
+            let bit_offset = self.index(self.bloom_hash(&mut hashes, item, k_i));
             self.bitmap.set(bit_offset);
         }
     }
@@ -154,7 +363,7 @@ impl<T: ?Sized> Bloom<T> {
     {
         let mut hashes = [0u64, 0u64];
         for k_i in 0..self.k_num {
-            let bit_offset = (self.bloom_hash(&mut hashes, item, k_i) % self.bitmap_bits) as usize;
+            let bit_offset = self.index(self.bloom_hash(&mut hashes, item, k_i));
             if self.bitmap.get(bit_offset) == false {
                 return false;
             }
@@ -170,7 +379,7 @@ impl<T: ?Sized> Bloom<T> {
         let mut hashes = [0u64, 0u64];
         let mut found = true;
         for k_i in 0..self.k_num {
-            let bit_offset = (self.bloom_hash(&mut hashes, item, k_i) % self.bitmap_bits) as usize;
+            let bit_offset = self.index(self.bloom_hash(&mut hashes, item, k_i));
             if self.bitmap.get(bit_offset) == false {
                 found = false;
                 self.bitmap.set(bit_offset);
@@ -185,28 +394,6 @@ impl<T: ?Sized> Bloom<T> {
         self.bitmap.as_slice()
     }
 
-    /// Create a bloom filter from a slice of bytes, previously generated with `as_slice`.
-    pub fn from_slice(bytes: &[u8]) -> Result<Self, &'static str> {
-        let bitmap = BitMap::from_slice(bytes)?;
-        let header = bitmap.header();
-        let k_num = BitMap::get_k_num(header);
-        let seed = BitMap::get_seed(header);
-        let mut k1 = [0u8; 16];
-        let mut k2 = [0u8; 16];
-        k1.copy_from_slice(&seed[0..16]);
-        k2.copy_from_slice(&seed[16..32]);
-        let sips = [Self::sip_new(&k1), Self::sip_new(&k2)];
-        let bitmap_bits = bitmap.len_bits();
-        let res = Self {
-            bitmap,
-            bitmap_bits,
-            k_num,
-            sips,
-            _phantom: PhantomData,
-        };
-        Ok(res)
-    }
-
     /// Serialize the bloom filter to an opaque byte vector.
     pub fn to_bytes(&self) -> Vec<u8> {
         self.bitmap.to_bytes()
@@ -217,28 +404,6 @@ impl<T: ?Sized> Bloom<T> {
         self.bitmap.into_bytes()
     }
 
-    /// Transform a byte vector into a bloom filter.
-    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, &'static str> {
-        let bitmap = BitMap::from_bytes(bytes)?;
-        let header = bitmap.header();
-        let k_num = BitMap::get_k_num(header);
-        let seed = BitMap::get_seed(header);
-        let mut k1 = [0u8; 16];
-        let mut k2 = [0u8; 16];
-        k1.copy_from_slice(&seed[0..16]);
-        k2.copy_from_slice(&seed[16..32]);
-        let sips = [Self::sip_new(&k1), Self::sip_new(&k2)];
-        let bitmap_bits = bitmap.len_bits();
-        let res = Self {
-            bitmap,
-            bitmap_bits,
-            k_num,
-            sips,
-            _phantom: PhantomData,
-        };
-        Ok(res)
-    }
-
     /// Return the number of hash functions used for `check` and `set`
     pub fn number_of_hash_functions(&self) -> u32 {
         self.k_num
@@ -259,12 +424,99 @@ impl<T: ?Sized> Bloom<T> {
         !self.bitmap.any()
     }
 
-    /// Return the seed used to generate the hash functions
-    pub fn seed(&self) -> [u8; 32] {
-        let mut seed = [0u8; 32];
-        seed[0..16].copy_from_slice(&self.sips[0].key());
-        seed[16..32].copy_from_slice(&self.sips[0].key());
-        seed
+    /// Approximate the number of distinct items inserted so far, based on
+    /// the fraction of bits that are set. Diverges towards infinity as the
+    /// filter saturates, since a fully-set filter can no longer tell how
+    /// many items produced that state; callers should treat a result close
+    /// to `items_count` (or beyond) as a signal to resize.
+    pub fn estimate_count(&self) -> f64 {
+        let m = self.bitmap_bits as f64;
+        let k = self.k_num as f64;
+        let x = self.bitmap.count_ones() as f64;
+        if x >= m {
+            return f64::INFINITY;
+        }
+        -(m / k) * (1.0 - x / m).ln()
+    }
+
+    /// Estimate the false-positive rate the filter is currently achieving,
+    /// based on the fraction of bits that are set.
+    pub fn estimated_fp_rate(&self) -> f64 {
+        let m = self.bitmap_bits as f64;
+        let k = self.k_num as f64;
+        let x = self.bitmap.count_ones() as f64;
+        (x / m).powf(k)
+    }
+
+    /// Merge `other` into this filter in place (a bitwise OR of the two
+    /// bitmaps), so that any item present in either filter before the call
+    /// is reported present afterwards. This lets per-shard filters built in
+    /// parallel (map-reduce style) be combined into one aggregate filter.
+    ///
+    /// Both filters must share the same bitmap size, number of hash
+    /// functions and build hasher, since merging filters built with
+    /// different hash keys would be meaningless.
+    pub fn union(&mut self, other: &Bloom<T, S>) -> Result<(), &'static str>
+    where
+        S: PartialEq,
+    {
+        self.check_compatible(other)?;
+        self.bitmap.or_with(&other.bitmap);
+        Ok(())
+    }
+
+    /// Intersect this filter with `other` in place (a bitwise AND of the two
+    /// bitmaps), so that only items that may be present in both filters
+    /// remain (possibly) present afterwards.
+    ///
+    /// Both filters must share the same bitmap size, number of hash
+    /// functions and build hasher.
+    pub fn intersect(&mut self, other: &Bloom<T, S>) -> Result<(), &'static str>
+    where
+        S: PartialEq,
+    {
+        self.check_compatible(other)?;
+        self.bitmap.and_with(&other.bitmap);
+        Ok(())
+    }
+
+    /// Test whether every bit set in `other` is also set in this filter,
+    /// i.e. every item `other` reports as (possibly) present, this filter
+    /// also reports as (possibly) present.
+    ///
+    /// Both filters must share the same bitmap size, number of hash
+    /// functions and build hasher.
+    pub fn contains_filter(&self, other: &Bloom<T, S>) -> Result<bool, &'static str>
+    where
+        S: PartialEq,
+    {
+        self.check_compatible(other)?;
+        Ok(other.bitmap.is_subset_of(&self.bitmap))
+    }
+
+    /// Test whether this filter is a subset of `other`: an alias for
+    /// `other.contains_filter(self)`.
+    pub fn is_subset(&self, other: &Bloom<T, S>) -> Result<bool, &'static str>
+    where
+        S: PartialEq,
+    {
+        other.contains_filter(self)
+    }
+
+    fn check_compatible(&self, other: &Bloom<T, S>) -> Result<(), &'static str>
+    where
+        S: PartialEq,
+    {
+        if self.bitmap_bits != other.bitmap_bits {
+            return Err("Filters have different bitmap sizes");
+        }
+        if self.k_num != other.k_num {
+            return Err("Filters use a different number of hash functions");
+        }
+        if self.build_hashers != other.build_hashers {
+            return Err("Filters were built with different hashers");
+        }
+        Ok(())
     }
 
     #[doc(hidden)]
@@ -277,34 +529,35 @@ impl<T: ?Sized> Bloom<T> {
         self
     }
 
-    #[inline]
-    fn sip_new(key: &[u8; 16]) -> SipHasher13 {
-        SipHasher13::new_with_key(key)
+    /// Give crate-internal wrappers (e.g. the journaling layer) read access
+    /// to the backing bitmap.
+    pub(crate) fn bitmap(&self) -> &BitMap {
+        &self.bitmap
     }
 
-    fn sync(&mut self) {
-        let seed = self.seed();
-        let header = self.bitmap.header_mut();
-        BitMap::set_k_num(header, self.k_num);
-        BitMap::set_seed(header, &seed);
+    /// Give crate-internal wrappers (e.g. the journaling layer) mutable
+    /// access to the backing bitmap.
+    pub(crate) fn bitmap_mut(&mut self) -> &mut BitMap {
+        &mut self.bitmap
     }
 
-    #[allow(dead_code)]
-    fn optimal_k_num(bitmap_bits: u64, items_count: usize) -> u32 {
-        let m = bitmap_bits as f64;
-        let n = items_count as f64;
-        let k_num = (m / n * f64::ln(2.0f64)).round() as u32;
-        cmp::max(k_num, 1)
+    /// Reduce a hash to a bit offset into the bitmap: a cheap `hash & mask`
+    /// for filters sized to a power of two, falling back to `hash %
+    /// bitmap_bits` otherwise.
+    #[inline]
+    pub(crate) fn index(&self, hash: u64) -> usize {
+        match self.mask {
+            Some(mask) => (hash & mask) as usize,
+            None => (hash % self.bitmap_bits) as usize,
+        }
     }
 
-    fn bloom_hash(&self, hashes: &mut [u64; 2], item: &T, k_i: u32) -> u64
+    pub(crate) fn bloom_hash(&self, hashes: &mut [u64; 2], item: &T, k_i: u32) -> u64
     where
         T: Hash,
     {
         if k_i < 2 {
-            let sip = &mut self.sips[k_i as usize].clone();
-            item.hash(sip);
-            let hash = sip.finish();
+            let hash = self.build_hashers[k_i as usize].hash_one(item);
             hashes[k_i as usize] = hash;
             hash
         } else {
@@ -325,10 +578,10 @@ mod serde_extensions {
         Deserializer, Serializer,
     };
 
-    pub fn serialize<S: Serializer, T: ?Sized>(
+    pub fn serialize<Ser: Serializer, T: ?Sized>(
         bloom: &Bloom<T>,
-        serializer: S,
-    ) -> Result<S::Ok, S::Error> {
+        serializer: Ser,
+    ) -> Result<Ser::Ok, Ser::Error> {
         serializer.serialize_bytes(bloom.as_slice())
     }
 
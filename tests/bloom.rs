@@ -1,4 +1,10 @@
-use bloomfilter::{reexports::getrandom::getrandom, Bloom};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::BuildHasherDefault;
+
+use bloomfilter::{
+    reexports::getrandom::getrandom, AtomicBloom, Bloom, BloomRef, BloomRefMut, CounterWidth,
+    CountingBloom, JournaledBloom,
+};
 
 #[test]
 #[cfg(feature = "random")]
@@ -33,6 +39,271 @@ fn bloom_test_clear() {
     assert!(!bloom.check(&k));
 }
 
+#[test]
+fn bloom_test_new_for_fp_rate_with_seed() {
+    let seed = [0u8; 32];
+    let items_count = 80;
+    let fp_p = 0.01;
+
+    let bitmap_size = Bloom::<Vec<u8>>::compute_bitmap_size(items_count, fp_p);
+    let expected_bits = (bitmap_size as u64) * 8;
+    let expected_k_num =
+        std::cmp::max(
+            ((expected_bits as f64) / (items_count as f64) * f64::ln(2.0)).round() as u32,
+            1,
+        );
+
+    let bloom = Bloom::<Vec<u8>>::new_for_fp_rate_with_seed(items_count, fp_p, &seed).unwrap();
+    assert_eq!(bloom.len(), expected_bits);
+    assert_eq!(bloom.number_of_hash_functions(), expected_k_num);
+
+    // A tighter false-positive rate should never produce a smaller bitmap.
+    let looser_bitmap_size = Bloom::<Vec<u8>>::compute_bitmap_size(items_count, 0.1);
+    assert!(bitmap_size > looser_bitmap_size);
+}
+
+#[test]
+fn bloom_test_pow2_layout_roundtrip() {
+    let seed = [0u8; 32];
+    let mut original = Bloom::<Vec<u8>>::new_pow2_for_fp_rate_with_seed(80, 0.01, &seed).unwrap();
+    assert!(original.len().is_power_of_two());
+    let k = vec![1u8, 2, 3];
+    original.set(&k);
+    assert!(original.check(&k));
+
+    let cloned = Bloom::from_bytes(original.to_bytes()).unwrap();
+    assert!(cloned.check(&k));
+    assert_eq!(original.to_bytes(), cloned.to_bytes());
+}
+
+#[test]
+fn counting_bloom_test_insert_remove() {
+    let seed = [0u8; 32];
+    let mut bloom =
+        CountingBloom::<Vec<u8>>::new_with_seed(10, 80, CounterWidth::Eight, &seed).unwrap();
+    let k = vec![1u8, 2, 3];
+    assert!(!bloom.check(&k));
+    bloom.insert(&k);
+    assert!(bloom.check(&k));
+    bloom.remove(&k);
+    assert!(!bloom.check(&k));
+}
+
+#[test]
+fn counting_bloom_test_round_trip_with_nonzero_seed() {
+    let mut seed = [0u8; 32];
+    for (i, byte) in seed.iter_mut().enumerate() {
+        *byte = i as u8 + 1;
+    }
+    let mut original =
+        CountingBloom::<u64>::new_for_fp_rate_with_seed(50, 0.01, CounterWidth::Eight, &seed)
+            .unwrap();
+    assert!(original.number_of_hash_functions() >= 2);
+    for i in 0..50u64 {
+        original.insert(&i);
+    }
+
+    let reloaded = CountingBloom::<u64>::from_bytes(&original.to_bytes()).unwrap();
+    for i in 0..50u64 {
+        assert!(reloaded.check(&i));
+    }
+}
+
+#[test]
+fn counting_bloom_test_remove_absent_item_is_noop() {
+    let seed = [0u8; 32];
+    let mut bloom =
+        CountingBloom::<Vec<u8>>::new_with_seed(10, 80, CounterWidth::Eight, &seed).unwrap();
+    let kept = vec![1u8, 2, 3];
+    let never_inserted = vec![9u8, 9, 9];
+    bloom.insert(&kept);
+    assert!(!bloom.check(&never_inserted));
+
+    // Removing an item that doesn't check present must not touch any
+    // counters, even ones it happens to share with a real member.
+    bloom.remove(&never_inserted);
+    assert!(bloom.check(&kept));
+}
+
+#[test]
+fn counting_bloom_test_saturating_counter_survives_one_remove() {
+    let seed = [0u8; 32];
+    let mut bloom =
+        CountingBloom::<Vec<u8>>::new_with_seed(10, 80, CounterWidth::Four, &seed).unwrap();
+    let k = vec![1u8, 2, 3];
+    // Push every counter `k` touches past its 4-bit saturation point (15).
+    for _ in 0..20 {
+        bloom.insert(&k);
+    }
+    bloom.remove(&k);
+    assert!(bloom.check(&k));
+}
+
+#[test]
+fn journaled_bloom_test_set_check_into_inner() {
+    let seed = [0u8; 32];
+    let inner = Bloom::<Vec<u8>>::new_with_seed(10, 80, &seed).unwrap();
+    let mut journaled = JournaledBloom::from_parts(inner);
+    let k = vec![1u8, 2, 3];
+    assert!(!journaled.check(&k));
+    journaled.set(&k);
+    assert!(journaled.check(&k));
+
+    let inner = journaled.into_inner();
+    assert!(inner.check(&k));
+}
+
+#[test]
+fn bloom_test_union_intersect_subset() {
+    let seed = [0u8; 32];
+    let mut a = Bloom::<Vec<u8>>::new_with_seed(10, 80, &seed).unwrap();
+    let mut b = Bloom::<Vec<u8>>::new_with_seed(10, 80, &seed).unwrap();
+    let shared = vec![1u8, 2, 3];
+    let only_b = vec![4u8, 5, 6];
+    a.set(&shared);
+    b.set(&shared);
+    b.set(&only_b);
+
+    assert!(a.is_subset(&b).unwrap());
+    assert!(!b.is_subset(&a).unwrap());
+    assert!(b.contains_filter(&a).unwrap());
+    assert!(!a.contains_filter(&b).unwrap());
+
+    let mut intersected = b.clone();
+    intersected.intersect(&a).unwrap();
+    assert!(intersected.check(&shared));
+    assert!(!intersected.check(&only_b));
+
+    let mut unioned = a.clone();
+    unioned.union(&b).unwrap();
+    assert!(unioned.check(&shared));
+    assert!(unioned.check(&only_b));
+}
+
+#[test]
+fn bloom_test_new_with_hashers() {
+    let build_hashers = [
+        BuildHasherDefault::<DefaultHasher>::default(),
+        BuildHasherDefault::<DefaultHasher>::default(),
+    ];
+    let mut bloom = Bloom::<Vec<u8>, BuildHasherDefault<DefaultHasher>>::new_with_hashers(
+        10,
+        80,
+        build_hashers,
+    )
+    .unwrap();
+    let k = vec![1u8, 2, 3];
+    assert!(!bloom.check(&k));
+    bloom.set(&k);
+    assert!(bloom.check(&k));
+}
+
+#[test]
+fn bloom_test_estimate_count_and_fp_rate() {
+    let seed = [0u8; 32];
+    let items_count = 80;
+    let mut bloom =
+        Bloom::<u64>::new_for_fp_rate_with_seed(items_count, 0.01, &seed).unwrap();
+    assert_eq!(bloom.estimate_count(), 0.0);
+    assert_eq!(bloom.estimated_fp_rate(), 0.0);
+
+    for i in 0..items_count as u64 {
+        bloom.set(&i);
+    }
+    let estimated = bloom.estimate_count();
+    assert!((estimated - items_count as f64).abs() < items_count as f64 * 0.2);
+    assert!(bloom.estimated_fp_rate() > 0.0 && bloom.estimated_fp_rate() < 1.0);
+
+    bloom.fill();
+    assert_eq!(bloom.estimate_count(), f64::INFINITY);
+}
+
+#[test]
+fn atomic_bloom_test_set_check_clear() {
+    let seed = [0u8; 32];
+    let bloom = AtomicBloom::<Vec<u8>>::new_with_seed(10, 80, &seed).unwrap();
+    let k = vec![1u8, 2, 3];
+    assert!(bloom.is_empty());
+    assert!(!bloom.check(&k));
+    bloom.set(&k);
+    assert!(!bloom.is_empty());
+    assert!(bloom.check(&k));
+    bloom.clear();
+    assert!(bloom.is_empty());
+    assert!(!bloom.check(&k));
+}
+
+#[test]
+fn journaled_bloom_test_drain_apply() {
+    let seed = [0u8; 32];
+    let writer_inner = Bloom::<u64>::new_with_seed(10, 80, &seed).unwrap();
+    let mut writer = JournaledBloom::from_parts(writer_inner);
+
+    let replica_inner = Bloom::<u64>::new_with_seed(10, 80, &seed).unwrap();
+    let mut replica = JournaledBloom::from_parts(replica_inner);
+
+    writer.set(&1u64);
+    writer.set(&2u64);
+    let journal = writer.drain_journal();
+    assert!(!journal.is_empty());
+    // A drained journal is a fresh baseline: draining again before any more
+    // writes returns nothing.
+    assert!(writer.drain_journal().is_empty());
+
+    replica.apply_journal(&journal);
+    assert!(replica.check(&1u64));
+    assert!(replica.check(&2u64));
+    assert!(!replica.check(&3u64));
+
+    writer.set(&3u64);
+    let second_journal = writer.drain_journal();
+    replica.apply_journal(&second_journal);
+    assert!(replica.check(&3u64));
+}
+
+#[test]
+fn bloom_test_round_trip_with_nonzero_seed() {
+    let mut seed = [0u8; 32];
+    for (i, byte) in seed.iter_mut().enumerate() {
+        *byte = i as u8 + 1;
+    }
+    let mut original = Bloom::<u64>::new_for_fp_rate_with_seed(50, 0.01, &seed).unwrap();
+    assert!(original.number_of_hash_functions() >= 2);
+    for i in 0..50u64 {
+        original.set(&i);
+    }
+
+    let reloaded = Bloom::<u64>::from_bytes(original.to_bytes()).unwrap();
+    for i in 0..50u64 {
+        assert!(reloaded.check(&i));
+    }
+}
+
+#[test]
+fn bloom_ref_test_check_and_set_with_nonzero_seed() {
+    let mut seed = [0u8; 32];
+    for (i, byte) in seed.iter_mut().enumerate() {
+        *byte = i as u8 + 1;
+    }
+    let mut original = Bloom::<u64>::new_for_fp_rate_with_seed(50, 0.01, &seed).unwrap();
+    assert!(original.number_of_hash_functions() >= 2);
+    for i in 0..50u64 {
+        original.set(&i);
+    }
+
+    let bytes = original.to_bytes();
+    let view = BloomRef::<u64>::from_slice(&bytes).unwrap();
+    for i in 0..50u64 {
+        assert!(view.check(&i));
+    }
+
+    let mut mut_bytes = bytes;
+    let mut mut_view = BloomRefMut::<u64>::from_slice(&mut mut_bytes).unwrap();
+    assert!(!mut_view.check(&100u64));
+    mut_view.set(&100u64);
+    assert!(mut_view.check(&100u64));
+}
+
 #[test]
 #[cfg(feature = "random")]
 fn bloom_test_load() {